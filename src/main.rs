@@ -2,7 +2,7 @@ mod target;
 
 use clap::Parser;
 use error_stack::ResultExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 fn main() -> AppResult {
@@ -16,8 +16,8 @@ fn main() -> AppResult {
     let input_content =
         std::fs::read_to_string(&config.input_name).change_context(AppError::FailedToReadInput)?;
 
-    let targets = target::Makefile::from_str(input_content.as_str())
-        .change_context(AppError::FailedToParseBuildFile)
+    let targets = target::parse_makefile(input_content.as_str())
+        .ok_or(AppError::FailedToParseBuildFile)
         .attach_printable("failed to parse the .msb file")?;
 
     if config.print_targets {
@@ -43,17 +43,28 @@ fn main() -> AppResult {
         }
         return Ok(());
     }
-    targets.build(&config.target);
+    targets
+        .build(
+            &config.target,
+            config.jobs(),
+            config.hash_cache_path(),
+            config.shell,
+        )
+        .change_context(AppError::FailedToBuild)
+        .attach_printable("failed to build the requested target")?;
 
     Ok(())
 }
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 enum AppError {
     #[error("A file system error occured when reading input build config")]
     FailedToReadInput,
     #[error("Failed to parse .msb file")]
     FailedToParseBuildFile,
+    #[error("Failed to build the requested target")]
+    FailedToBuild,
 }
 
 type AppResult = error_stack::Result<(), AppError>;
@@ -72,9 +83,38 @@ struct Config {
     /// Print the available targets in this .msb file
     #[arg(long)]
     print_targets: bool,
+
+    /// Maximum number of targets to build concurrently (defaults to the host's available parallelism)
+    #[arg(short = 'j', long)]
+    jobs: Option<usize>,
+
+    /// Check staleness by content hash (cached in .msb-cache) instead of relying purely on mtimes
+    #[arg(long)]
+    hash: bool,
+
+    /// Run each command line through the platform shell instead of tokenizing it ourselves
+    #[arg(long)]
+    shell: bool,
 }
 
 impl Config {
+    fn jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+    }
+
+    fn hash_cache_path(&self) -> Option<PathBuf> {
+        self.hash.then(|| {
+            self.input_name
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join(target::HASH_CACHE_FILE_NAME)
+        })
+    }
+
     fn validate(&self) -> Result<(), String> {
         if !self.input_name.exists() || !self.input_name.is_file() {
             return Err(format!(
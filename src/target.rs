@@ -1,7 +1,8 @@
 use nom::{
+    branch::alt,
     bytes::complete::{tag, take_until, take_while1},
     character::complete::{alphanumeric1, multispace0, multispace1},
-    combinator::{map, opt},
+    combinator::{map, opt, verify},
     multi::{many0, separated_list0},
     sequence::{delimited, preceded, separated_pair},
     IResult, Parser,
@@ -9,13 +10,116 @@ use nom::{
 
 use error_stack::ResultExt;
 use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
     fs,
-    path::Path,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex},
+    thread,
     time::{Instant, SystemTime},
 };
 use thiserror::Error;
 
-#[derive(Debug)]
+/// Name of the content-hash cache file `Makefile::build` maintains next to the build
+/// file when hash-based staleness checking (`--hash`) is enabled.
+pub const HASH_CACHE_FILE_NAME: &str = ".msb-cache";
+
+/// Splits a command string into argv the way a POSIX shell would: single quotes
+/// take everything literally, double quotes still allow `\"`/`\\`/`\$` escapes, a
+/// bare backslash escapes the next character, and unquoted whitespace separates
+/// tokens.
+fn tokenize(cmd: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut chars = cmd.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_token = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    current.push(c);
+                }
+            }
+            '"' => {
+                in_token = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if matches!(chars.peek(), Some('"' | '\\' | '$')) => {
+                            current.push(chars.next().unwrap());
+                        }
+                        c => current.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_token = true;
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                in_token = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Builds the `Command` for a single build-step line, or `None` for a blank line.
+/// With `use_shell`, the whole line is handed to the platform shell verbatim
+/// (honoring redirections, globs, etc.); otherwise it's tokenized ourselves.
+fn build_command(cmd: &str, use_shell: bool) -> Option<std::process::Command> {
+    if use_shell {
+        if cmd.trim().is_empty() {
+            return None;
+        }
+        let mut command = std::process::Command::new(if cfg!(windows) { "cmd" } else { "sh" });
+        if cfg!(windows) {
+            command.arg("/C").arg(cmd);
+        } else {
+            command.arg("-c").arg(cmd);
+        }
+        return Some(command);
+    }
+
+    let parts = tokenize(cmd);
+    let (exe, args) = parts.split_first()?;
+    let mut command = std::process::Command::new(exe);
+    command.args(args);
+    Some(command)
+}
+
+/// Extracts the signal that killed a process, if any (always `None` off Unix).
+fn terminating_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Target {
     name: String,
     outputs: Vec<String>,
@@ -87,17 +191,22 @@ impl Target {
             let Ok(dep_mod_time) = fs::metadata(dep_path).and_then(|meta| meta.modified()) else {
                 return false;
             };
-            if dep_mod_time > target_mod_time {
+            if dep_mod_time > target_mod_time && !makefile.hash_unchanged(dep) {
                 return false;
             }
         }
 
         for dep_name in &self.target_dependencies {
-            if let Some(dep_target) = makefile.get_target(dep_name) {
+            if let Some(dep_target) = makefile.resolve_target(dep_name) {
                 let Some(dep_mod_time) = dep_target.get_min_output_time() else {
                     return false;
                 };
-                if dep_mod_time > target_mod_time {
+                if dep_mod_time > target_mod_time
+                    && !dep_target
+                        .outputs
+                        .iter()
+                        .all(|o| makefile.hash_unchanged(o))
+                {
                     return false;
                 }
             } else {
@@ -107,58 +216,117 @@ impl Target {
         true
     }
 
-    pub fn build(&self, makefile: &Makefile) -> BuildResult<()> {
-        let pre_build = Instant::now();
+    /// Records the current content hash of every file dependency and output of this
+    /// target (target dependencies record their own outputs when they build), so a future `is_up_to_date` can tell a touch-only edit from a real one.
+    fn update_hash_cache(&self, makefile: &Makefile) {
+        if !makefile.hash_mode {
+            return;
+        }
+        let mut cache = makefile.hash_cache.lock().unwrap();
+        for path in self.outputs.iter().chain(self.file_dependencies.iter()) {
+            if let Some(digest) = digest_file(Path::new(path)) {
+                cache.insert(path.clone(), digest);
+            }
+        }
+    }
 
-        for dep in &self.target_dependencies {
-            match makefile.get_target(dep) {
-                None => {
-                    return Err(BuildError::FailedToFindTargetForDependency {
-                        target_name: self.name.clone(),
-                        dependency_name: dep.to_string(),
+    /// All prerequisite paths for this target: its file dependencies followed by
+    /// the outputs of its target dependencies (resolved, so a stem-matched pattern
+    /// dependency like `targets(%.o)` contributes its built `.o` file, not its name).
+    fn prerequisites(&self, makefile: &Makefile) -> Vec<String> {
+        let mut prerequisites = self.file_dependencies.clone();
+        for dep_name in &self.target_dependencies {
+            match makefile.resolve_target(dep_name) {
+                Some(dep) => prerequisites.extend(dep.outputs),
+                None => prerequisites.push(dep_name.clone()),
+            }
+        }
+        prerequisites
+    }
+
+    /// Expands `$(NAME)` variable references and the automatic variables `$@`
+    /// (first output), `$<` (first prerequisite) and `$^` (all prerequisites,
+    /// i.e. file dependencies plus target dependencies' outputs) in a single
+    /// left-to-right scan of `cmd`.
+    fn expand(&self, cmd: &str, makefile: &Makefile) -> BuildResult<String> {
+        let prerequisites = self.prerequisites(makefile);
+        let mut out = String::with_capacity(cmd.len());
+        let mut chars = cmd.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                out.push(c);
+                continue;
+            }
+            match chars.peek() {
+                Some('@') => {
+                    chars.next();
+                    out.push_str(self.outputs.first().map_or(&self.name[..], String::as_str));
+                }
+                Some('<') => {
+                    chars.next();
+                    if let Some(first) = prerequisites.first() {
+                        out.push_str(first);
                     }
-                    .into());
                 }
-                Some(target_dep) => target_dep.build(makefile)?,
+                Some('^') => {
+                    chars.next();
+                    out.push_str(&prerequisites.join(" "));
+                }
+                Some('(') => {
+                    chars.next();
+                    let name: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                    match makefile.variables.get(&name) {
+                        Some(value) => out.push_str(value),
+                        None => return Err(BuildError::UndefinedVariable { name }.into()),
+                    }
+                }
+                _ => out.push('$'),
             }
         }
+        Ok(out)
+    }
+
+    /// Builds this target in isolation: checks staleness and runs its commands.
+    /// Building its dependencies first is the caller's responsibility.
+    fn build(&self, makefile: &Makefile) -> BuildResult<()> {
+        let pre_build = Instant::now();
 
         if self.is_up_to_date(makefile) {
             println!("Target `{}` is up-to-date, skipping build.", self.name);
             return Ok(());
         }
 
-        // TODO: Proper command line parsing
         let mut children = vec![];
         for cmd in &self.commands {
-            let parts: Vec<&str> = cmd.split_whitespace().collect();
-            if parts.is_empty() {
+            let cmd = self.expand(cmd, makefile)?;
+            let Some(mut command) = build_command(&cmd, makefile.use_shell) else {
                 continue;
-            }
-            let exe = parts[0];
-            let args = &parts[1..];
-            let mut command = std::process::Command::new(exe);
-            command.args(args);
-            children.push(
-                command
-                    .spawn()
-                    .change_context(BuildError::FailedToSpawnProcess {
-                        cmd: cmd.to_string(),
-                    })?,
-            );
-        }
-
-        for mut child in children {
-            let exit_code = child
+            };
+            let child = command
+                .spawn()
+                .change_context(BuildError::FailedToSpawnProcess { cmd: cmd.clone() })?;
+            children.push((cmd, child));
+        }
+
+        for (cmd, mut child) in children {
+            let status = child
                 .wait()
-                .change_context(BuildError::BuildProcessFailedToStart)?
-                .code()
-                .ok_or(BuildError::FailedToGetChildExitCode)?;
-            if exit_code != 0 {
-                return Err(BuildError::BuildProcessQuitWithNonZero.into());
+                .change_context(BuildError::BuildProcessFailedToStart)?;
+            match status.code() {
+                Some(0) => {}
+                Some(code) => return Err(BuildError::CommandFailed { cmd, code }.into()),
+                None => {
+                    return Err(BuildError::TerminatedBySignal {
+                        cmd,
+                        signal: terminating_signal(&status),
+                    }
+                    .into())
+                }
             }
         }
 
+        self.update_hash_cache(makefile);
+
         println!(
             "Building target `{}` took: {:.2?}",
             self.name,
@@ -168,9 +336,123 @@ impl Target {
     }
 }
 
+/// An inference rule such as `%.o: %.c` that synthesizes a concrete `Target`
+/// for any requested output matching its pattern.
+#[derive(Debug, Clone)]
+struct PatternRule {
+    output_pattern: String,
+    file_patterns: Vec<String>,
+    target_dependencies: Vec<String>,
+    commands: Vec<String>,
+}
+
+impl PatternRule {
+    /// If `requested` matches this rule's output pattern, returns the stem that `%` stands for.
+    fn stem_for(&self, requested: &str) -> Option<String> {
+        let (prefix, suffix) = self.output_pattern.split_once('%')?;
+        let stem = requested.strip_prefix(prefix)?.strip_suffix(suffix)?;
+        Some(stem.to_string())
+    }
+
+    /// Substitutes `stem` into the rule's `%`-bearing file dependencies, target
+    /// dependencies and commands to synthesize a concrete `Target` named `name`.
+    fn instantiate(&self, name: &str, stem: &str) -> Target {
+        let file_dependencies = self
+            .file_patterns
+            .iter()
+            .map(|file| file.replace('%', stem))
+            .collect();
+        let target_dependencies = self
+            .target_dependencies
+            .iter()
+            .map(|dep| dep.replace('%', stem))
+            .collect();
+        let commands = self
+            .commands
+            .iter()
+            .map(|cmd| cmd.replace('%', stem))
+            .collect();
+
+        Target::new(
+            name.to_string(),
+            vec![name.to_string()],
+            file_dependencies,
+            target_dependencies,
+            commands,
+        )
+    }
+}
+
+/// Size + fast content hash of a dependency file, used for `--hash` staleness checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileDigest {
+    size: u64,
+    hash: u64,
+}
+
+fn digest_file(path: &Path) -> Option<FileDigest> {
+    let bytes = fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    Some(FileDigest {
+        size: bytes.len() as u64,
+        hash: hasher.finish(),
+    })
+}
+
+fn load_hash_cache(path: &Path) -> HashMap<String, FileDigest> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let path = fields.next()?;
+            let size = fields.next()?.parse().ok()?;
+            let hash = fields.next()?.parse().ok()?;
+            Some((path.to_string(), FileDigest { size, hash }))
+        })
+        .collect()
+}
+
+fn save_hash_cache(path: &Path, cache: &HashMap<String, FileDigest>) {
+    let content: String = cache
+        .iter()
+        .map(|(path, digest)| format!("{path}\t{}\t{}\n", digest.size, digest.hash))
+        .collect();
+    let _ = fs::write(path, content);
+}
+
 #[derive(Debug)]
 pub struct Makefile {
     targets: Vec<Target>,
+    patterns: Vec<PatternRule>,
+    variables: HashMap<String, String>,
+    /// Targets synthesized from a pattern rule, cached so repeated lookups of the
+    /// same stemmed output (e.g. as both an output and a dependency) stay stable.
+    synthesized: Mutex<HashMap<String, Target>>,
+    /// Whether `is_up_to_date` may fall back to content hashes (`--hash`).
+    hash_mode: bool,
+    /// Immutable snapshot of the on-disk cache as it was before this invocation
+    /// started building anything. `hash_unchanged` reads only from here, so a
+    /// dependency rebuilt earlier in the same run can't be compared against its
+    /// own fresh hash and mistaken for unchanged.
+    hash_cache_snapshot: HashMap<String, FileDigest>,
+    /// path -> digest, updated as targets build and flushed back to the cache
+    /// file once the whole build succeeds (a failed build leaves it unwritten).
+    hash_cache: Mutex<HashMap<String, FileDigest>>,
+    /// Whether to dispatch whole command lines through the platform shell (`--shell`)
+    /// instead of tokenizing them ourselves.
+    use_shell: bool,
+}
+
+/// DFS node coloring used by `Makefile::resolve` to detect dependency cycles.
+/// Nodes absent from the map are implicitly white (unvisited).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Color {
+    Gray,
+    Black,
 }
 
 #[derive(Debug, Error)]
@@ -184,16 +466,161 @@ pub enum BuildError {
     },
     #[error("Failed to find target `{target_name}` to build")]
     FailedToFindTargetToBuild { target_name: String },
+    #[error("Dependency cycle detected: {}", path.join(" -> "))]
+    DependencyCycle { path: Vec<String> },
+    #[error("Undefined variable referenced: $({name})")]
+    UndefinedVariable { name: String },
     #[error("Some build process failed to start")]
     BuildProcessFailedToStart,
-    #[error("Failed to get build process exit code")]
-    FailedToGetChildExitCode,
-    #[error("Build process quit with non-zero exit code")]
-    BuildProcessQuitWithNonZero,
+    #[error("Command `{cmd}` failed with exit code {code}")]
+    CommandFailed { cmd: String, code: i32 },
+    #[error(
+        "Command `{cmd}` was terminated by signal{}",
+        signal.map(|s| format!(" {s}")).unwrap_or_default()
+    )]
+    TerminatedBySignal { cmd: String, signal: Option<i32> },
 }
 
 pub type BuildResult<T> = error_stack::Result<T, BuildError>;
 
+/// A counting semaphore used to cap how many targets build concurrently.
+struct JobSemaphore {
+    tokens: Mutex<usize>,
+    available: Condvar,
+}
+
+impl JobSemaphore {
+    fn new(permits: usize) -> Self {
+        Self {
+            tokens: Mutex::new(permits.max(1)),
+            available: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut tokens = self.tokens.lock().unwrap();
+        while *tokens == 0 {
+            tokens = self.available.wait(tokens).unwrap();
+        }
+        *tokens -= 1;
+    }
+
+    fn release(&self) {
+        *self.tokens.lock().unwrap() += 1;
+        self.available.notify_one();
+    }
+}
+
+/// Drives a DAG of targets to completion, building independent targets concurrently
+/// while respecting `target_dependencies` order, bounded by a `JobSemaphore`.
+struct Scheduler {
+    /// Reverse edges: target name -> targets that depend on it.
+    dependents: HashMap<String, Vec<String>>,
+    /// Number of not-yet-built dependencies remaining for each target.
+    remaining: Mutex<HashMap<String, usize>>,
+    ready: Mutex<VecDeque<String>>,
+    work_available: Condvar,
+    done: Mutex<usize>,
+    total: usize,
+    error: Mutex<Option<error_stack::Report<BuildError>>>,
+    jobs: JobSemaphore,
+}
+
+impl Scheduler {
+    fn new(dependencies: HashMap<String, Vec<String>>, jobs: usize) -> Self {
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        let mut remaining = HashMap::new();
+        for (name, deps) in &dependencies {
+            remaining.insert(name.clone(), deps.len());
+            for dep in deps {
+                dependents
+                    .entry(dep.clone())
+                    .or_default()
+                    .push(name.clone());
+            }
+        }
+        let ready: VecDeque<String> = remaining
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(name, _)| name.clone())
+            .collect();
+        let total = remaining.len();
+
+        Self {
+            dependents,
+            remaining: Mutex::new(remaining),
+            ready: Mutex::new(ready),
+            work_available: Condvar::new(),
+            done: Mutex::new(0),
+            total,
+            error: Mutex::new(None),
+            jobs: JobSemaphore::new(jobs),
+        }
+    }
+
+    /// Marks `name` as finished, unlocking any targets whose last dependency was `name`.
+    fn complete(&self, name: &str) {
+        let mut remaining = self.remaining.lock().unwrap();
+        let mut ready = self.ready.lock().unwrap();
+        if let Some(dependents) = self.dependents.get(name) {
+            for dependent in dependents {
+                if let Some(count) = remaining.get_mut(dependent) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push_back(dependent.clone());
+                    }
+                }
+            }
+        }
+        drop(remaining);
+        drop(ready);
+        *self.done.lock().unwrap() += 1;
+        self.work_available.notify_all();
+    }
+
+    fn fail(&self, err: error_stack::Report<BuildError>) {
+        let mut error = self.error.lock().unwrap();
+        if error.is_none() {
+            *error = Some(err);
+        }
+        self.work_available.notify_all();
+    }
+
+    fn run(&self, makefile: &Makefile) -> BuildResult<()> {
+        thread::scope(|scope| loop {
+            let name = {
+                let mut ready = self.ready.lock().unwrap();
+                loop {
+                    if self.error.lock().unwrap().is_some()
+                        || *self.done.lock().unwrap() == self.total
+                    {
+                        return;
+                    }
+                    if let Some(name) = ready.pop_front() {
+                        break name;
+                    }
+                    ready = self.work_available.wait(ready).unwrap();
+                }
+            };
+
+            self.jobs.acquire();
+            scope.spawn(move || {
+                let target = makefile
+                    .resolve_target(&name)
+                    .expect("target scheduled for build must exist in the makefile");
+                let result = target.build(makefile);
+                self.jobs.release();
+                match result {
+                    Ok(()) => self.complete(&name),
+                    Err(err) => self.fail(err),
+                }
+            });
+        });
+
+        self.error.lock().unwrap().take().map_or(Ok(()), Err)
+    }
+}
+
 impl Makefile {
     pub fn get_targets(&self) -> &Vec<Target> {
         &self.targets
@@ -203,13 +630,140 @@ impl Makefile {
         self.targets.iter().find(|t| t.name == name)
     }
 
-    pub fn build(self, target: &str) -> BuildResult<()> {
-        self.get_target(target)
+    /// Resolves `name` to a concrete target: an explicit one if present, otherwise
+    /// one synthesized from the first matching pattern rule.
+    fn resolve_target(&self, name: &str) -> Option<Target> {
+        if let Some(target) = self.get_target(name) {
+            return Some(target.clone());
+        }
+        if let Some(cached) = self.synthesized.lock().unwrap().get(name) {
+            return Some(cached.clone());
+        }
+        for pattern in &self.patterns {
+            if let Some(stem) = pattern.stem_for(name) {
+                let target = pattern.instantiate(name, &stem);
+                self.synthesized
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_string(), target.clone());
+                return Some(target);
+            }
+        }
+        None
+    }
+
+    /// In `--hash` mode, whether `path`'s current content still matches the digest
+    /// recorded for it in the on-disk cache as it stood before this run, letting a mtime-stale file count as
+    /// up-to-date after a no-op `git checkout` or `touch`.
+    fn hash_unchanged(&self, path: &str) -> bool {
+        if !self.hash_mode {
+            return false;
+        }
+        let Some(current) = digest_file(Path::new(path)) else {
+            return false;
+        };
+        self.hash_cache_snapshot.get(path) == Some(&current)
+    }
+
+    /// Performs a DFS over the target-dependency graph reachable from `target`,
+    /// coloring nodes white/gray/black, and fails fast if a gray node is revisited
+    /// (i.e. a dependency cycle), rather than recursing until the stack overflows.
+    fn resolve(&self, target: &str) -> BuildResult<()> {
+        let mut colors: HashMap<String, Color> = HashMap::new();
+        let mut path = Vec::new();
+        self.visit(target, &mut colors, &mut path)
+    }
+
+    fn visit(
+        &self,
+        name: &str,
+        colors: &mut HashMap<String, Color>,
+        path: &mut Vec<String>,
+    ) -> BuildResult<()> {
+        match colors.get(name) {
+            Some(Color::Black) => return Ok(()),
+            Some(Color::Gray) => {
+                let cycle_start = path.iter().position(|n| n == name).unwrap_or(0);
+                let mut cycle = path[cycle_start..].to_vec();
+                cycle.push(name.to_string());
+                return Err(BuildError::DependencyCycle { path: cycle }.into());
+            }
+            _ => {}
+        }
+
+        colors.insert(name.to_string(), Color::Gray);
+        path.push(name.to_string());
+
+        if let Some(t) = self.resolve_target(name) {
+            for dep in &t.target_dependencies {
+                self.visit(dep, colors, path)?;
+            }
+        }
+
+        path.pop();
+        colors.insert(name.to_string(), Color::Black);
+        Ok(())
+    }
+
+    /// Builds `target` and everything it (transitively) depends on, running up to
+    /// `jobs` independent targets at once. When `hash_cache_path` is set, staleness
+    /// checks fall back to content hashes cached at that path instead of relying
+    /// purely on mtimes.
+    pub fn build(
+        mut self,
+        target: &str,
+        jobs: usize,
+        hash_cache_path: Option<PathBuf>,
+        use_shell: bool,
+    ) -> BuildResult<()> {
+        self.hash_mode = hash_cache_path.is_some();
+        if let Some(path) = &hash_cache_path {
+            let on_disk = load_hash_cache(path);
+            self.hash_cache_snapshot = on_disk.clone();
+            self.hash_cache = Mutex::new(on_disk);
+        }
+        self.use_shell = use_shell;
+
+        let root = self
+            .resolve_target(target)
             .ok_or(BuildError::FailedToFindTargetToBuild {
                 target_name: target.to_string(),
             })?
-            .build(&self)?;
-        Ok(())
+            .name;
+
+        self.resolve(&root)?;
+
+        let mut dependencies: HashMap<String, Vec<String>> = HashMap::new();
+        let mut stack = vec![root];
+        while let Some(name) = stack.pop() {
+            if dependencies.contains_key(&name) {
+                continue;
+            }
+            let deps = self
+                .resolve_target(&name)
+                .expect("name was discovered via target_dependencies or is the root")
+                .target_dependencies()
+                .to_vec();
+            for dep in &deps {
+                if self.resolve_target(dep).is_none() {
+                    return Err(BuildError::FailedToFindTargetForDependency {
+                        target_name: name.clone(),
+                        dependency_name: dep.clone(),
+                    }
+                    .into());
+                }
+                stack.push(dep.clone());
+            }
+            dependencies.insert(name, deps);
+        }
+
+        let result = Scheduler::new(dependencies, jobs).run(&self);
+        if result.is_ok() {
+            if let Some(path) = &hash_cache_path {
+                save_hash_cache(path, &self.hash_cache.lock().unwrap());
+            }
+        }
+        result
     }
 }
 
@@ -281,6 +835,14 @@ fn parse_commands(input: &str) -> IResult<&str, Vec<String>> {
     Ok((input, commands))
 }
 
+fn parse_assignment(input: &str) -> IResult<&str, (String, String)> {
+    let (input, _) = multispace0(input)?;
+    let (input, name) = identifier(input)?;
+    let (input, _) = delimited(multispace0, tag("="), multispace0).parse(input)?;
+    let (input, value) = take_while1(|c: char| c != '\n')(input)?;
+    Ok((input, (name.to_string(), value.trim().to_string())))
+}
+
 fn parse_target(input: &str) -> IResult<&str, Target> {
     let (input, _) = multispace0(input)?;
     let (input, _) = tag("target")(input)?;
@@ -299,7 +861,191 @@ fn parse_target(input: &str) -> IResult<&str, Target> {
     ))
 }
 
+/// Like `identifier`, but permissive enough to capture pattern names like `%.o`.
+fn pattern_identifier(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| !c.is_whitespace())(input)
+}
+
+fn parse_pattern_target(input: &str) -> IResult<&str, PatternRule> {
+    let (input, _) = multispace0(input)?;
+    let (input, _) = tag("target")(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, output_pattern) =
+        verify(pattern_identifier, |s: &str| s.contains('%')).parse(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, (files, target_deps)) = parse_dependencies(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, commands) = parse_commands(input)?;
+
+    Ok((
+        input,
+        PatternRule {
+            output_pattern: output_pattern.to_string(),
+            file_patterns: files,
+            target_dependencies: target_deps,
+            commands,
+        },
+    ))
+}
+
+enum TargetOrPattern {
+    Target(Target),
+    Pattern(PatternRule),
+}
+
+fn parse_target_or_pattern(input: &str) -> IResult<&str, TargetOrPattern> {
+    alt((
+        map(parse_pattern_target, TargetOrPattern::Pattern),
+        map(parse_target, TargetOrPattern::Target),
+    ))
+    .parse(input)
+}
+
+/// A `ifeq`/`ifneq`/`ifdef` condition guarding a block of targets or commands.
+#[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+enum Condition {
+    IfEq(String, String),
+    IfNeq(String, String),
+    IfDef(String),
+}
+
+fn eval_condition(condition: &Condition, variables: &HashMap<String, String>) -> bool {
+    match condition {
+        Condition::IfEq(name, value) => {
+            variables.get(name).map(String::as_str) == Some(value.as_str())
+        }
+        Condition::IfNeq(name, value) => {
+            variables.get(name).map(String::as_str) != Some(value.as_str())
+        }
+        Condition::IfDef(name) => variables.contains_key(name),
+    }
+}
+
+fn parse_ifeq(input: &str) -> IResult<&str, (String, String)> {
+    delimited(
+        tag("ifeq("),
+        separated_pair(
+            map(target_identifier, str::to_string),
+            delimited(multispace0, tag(","), multispace0),
+            map(target_identifier, str::to_string),
+        ),
+        tag(")"),
+    )
+    .parse(input)
+}
+
+fn parse_ifneq(input: &str) -> IResult<&str, (String, String)> {
+    delimited(
+        tag("ifneq("),
+        separated_pair(
+            map(target_identifier, str::to_string),
+            delimited(multispace0, tag(","), multispace0),
+            map(target_identifier, str::to_string),
+        ),
+        tag(")"),
+    )
+    .parse(input)
+}
+
+fn parse_ifdef(input: &str) -> IResult<&str, String> {
+    delimited(tag("ifdef("), map(identifier, str::to_string), tag(")")).parse(input)
+}
+
+fn parse_condition(input: &str) -> IResult<&str, Condition> {
+    alt((
+        map(parse_ifeq, |(name, value)| Condition::IfEq(name, value)),
+        map(parse_ifneq, |(name, value)| Condition::IfNeq(name, value)),
+        map(parse_ifdef, Condition::IfDef),
+    ))
+    .parse(input)
+}
+
+/// Consumes lines up to (and including) the `endif` matching the condition that was
+/// just opened, recursing into any conditional nested inside either branch, and
+/// returns the text of whichever branch `condition_true` selects.
+fn take_conditional_block<'a>(
+    lines: &mut std::iter::Peekable<std::str::Lines<'a>>,
+    variables: &HashMap<String, String>,
+    condition_true: bool,
+) -> String {
+    let mut then_branch = String::new();
+    let mut else_branch = String::new();
+    let mut in_else = false;
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed == "endif" {
+            break;
+        }
+        if trimmed == "else" && !in_else {
+            in_else = true;
+            continue;
+        }
+        let branch = if in_else {
+            &mut else_branch
+        } else {
+            &mut then_branch
+        };
+        if let Ok((_, nested)) = parse_condition(trimmed) {
+            let nested_true = eval_condition(&nested, variables);
+            branch.push_str(&take_conditional_block(lines, variables, nested_true));
+        } else {
+            branch.push_str(line);
+            branch.push('\n');
+        }
+    }
+
+    if condition_true {
+        then_branch
+    } else {
+        else_branch
+    }
+}
+
+/// Evaluates `ifeq`/`ifneq`/`ifdef` blocks against `variables`, keeping only the
+/// taken branch's lines so conditionals can wrap whole targets or single command
+/// lines alike. Runs as a pre-pass before `many0(parse_target_or_pattern)`.
+fn strip_conditionals(input: &str, variables: &HashMap<String, String>) -> String {
+    let mut lines = input.lines().peekable();
+    let mut out = String::new();
+    while let Some(line) = lines.next() {
+        if let Ok((_, condition)) = parse_condition(line.trim()) {
+            let taken = eval_condition(&condition, variables);
+            out.push_str(&take_conditional_block(&mut lines, variables, taken));
+        } else {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
 pub fn parse_makefile(input: &str) -> Option<Makefile> {
-    let (_, targets) = many0(parse_target).parse(input).ok()?;
-    Some(Makefile { targets })
+    let (input, variables) = many0(parse_assignment).parse(input).ok()?;
+    let variables: HashMap<String, String> = variables.into_iter().collect();
+    let filtered = strip_conditionals(input, &variables);
+    let (_, items) = many0(parse_target_or_pattern)
+        .parse(filtered.as_str())
+        .ok()?;
+
+    let mut targets = Vec::new();
+    let mut patterns = Vec::new();
+    for item in items {
+        match item {
+            TargetOrPattern::Target(t) => targets.push(t),
+            TargetOrPattern::Pattern(p) => patterns.push(p),
+        }
+    }
+
+    Some(Makefile {
+        targets,
+        patterns,
+        variables,
+        synthesized: Mutex::new(HashMap::new()),
+        hash_mode: false,
+        hash_cache_snapshot: HashMap::new(),
+        hash_cache: Mutex::new(HashMap::new()),
+        use_shell: false,
+    })
 }